@@ -1,3 +1,10 @@
+// The bot is built on serenity's `StandardFramework`, which serenity 0.12 marks
+// deprecated ahead of its 0.13 removal in favour of `poise`; we keep the existing
+// command layout until that migration, so silence the churn here. The explicit
+// trailing `return` is this crate's house style.
+#![allow(deprecated)]
+#![allow(clippy::needless_return)]
+
 //! Requires the "client", "standard_framework", and "voice" features be enabled in your
 //! Cargo.toml, like so:
 //!
@@ -6,33 +13,54 @@
 //! git = "https://github.com/serenity-rs/serenity.git"
 //! features = ["client", standard_framework", "voice"]
 //! ```
-use std::{env, sync::Arc};
+use std::{collections::HashMap, env, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
 
 // This trait adds the `register_songbird` and `register_songbird_with` methods
 // to the client builder below, making it easy to install this voice client.
 // The voice client can be retrieved in any command using `songbird::get(ctx).await`.
-use songbird::{Call, SerenityInit};
+use songbird::{Call, Event, SerenityInit, TrackEvent};
 
 // Import the `Context` to handle commands.
-use serenity::{client::{Context, bridge::gateway::ShardManager}, model::guild::GuildStatus, prelude::Mutex};
+use serenity::{client::Context, gateway::ShardManager, prelude::{GatewayIntents, Mutex, TypeMapKey}};
 
 use serenity::{
 	async_trait,
+	builder::{CreateEmbed, CreateMessage},
 	client::{Client, EventHandler},
 	framework::{
 		StandardFramework,
 		standard::{
+			Configuration,
 			Args, CommandResult,
 			macros::{command, group},
 		},
 	},
-	model::{channel::Message, gateway::Ready},
+	http::Http,
+	model::{channel::Message, gateway::Ready, id::{ChannelId, GuildId}},
 	Result as SerenityResult,
 };
 
+// A shared `reqwest::Client` kept in the client's `TypeMap` so every `YoutubeDl`
+// source reuses the same connection pool instead of spawning its own, the same
+// way the upstream songbird examples thread an `HttpKey` through.
+struct HttpKey;
+
+impl TypeMapKey for HttpKey {
+	type Value = reqwest::Client;
+}
+
 struct HandlerState {
 	ctx: Option<Context>,
-	guilds: Vec<GuildStatus>,
+	guilds: Vec<GuildId>,
+	// Text channel to post queue notifications into, per guild. Updated by
+	// whichever `play`/`playurl` command last targeted the guild; the CLI never
+	// sets it, so console-queued tracks announce into that same channel.
+	announce_channels: HashMap<GuildId, ChannelId>,
+	// Number of tracks we've enqueued but not yet seen end, per guild. Bumped on
+	// every enqueue and decremented on `TrackEvent::End`; reaching zero is our
+	// explicit "queue drained" signal, independent of songbird's internal pop
+	// ordering.
+	queue_depth: HashMap<GuildId, Arc<AtomicUsize>>,
 }
 
 
@@ -41,8 +69,123 @@ impl HandlerState {
 		HandlerState {
 			ctx: None,
 			guilds: Vec::new(),
+			announce_channels: HashMap::new(),
+			queue_depth: HashMap::new(),
 		}
 	}
+
+	fn queue_counter(&mut self, guild_id: GuildId) -> Arc<AtomicUsize> {
+		return self.queue_depth
+			.entry(guild_id)
+			.or_insert_with(|| Arc::new(AtomicUsize::new(0)))
+			.clone();
+	}
+}
+
+/// Title of an enqueued track, stashed on its `TrackHandle` so the
+/// `TrackEvent::Play` notifier can name the clip that just started.
+struct TrackTitleKey;
+
+impl songbird::typemap::TypeMapKey for TrackTitleKey {
+	type Value = String;
+}
+
+struct HandlerStateKey;
+
+impl TypeMapKey for HandlerStateKey {
+	type Value = Arc<Mutex<HandlerState>>;
+}
+
+/// Posts queue progress embeds into the guild's announce channel as songbird
+/// fires `TrackEvent::Play` / `TrackEvent::End`.
+struct TrackNotifier {
+	guild_id: GuildId,
+	http: Arc<Http>,
+	state: Arc<Mutex<HandlerState>>,
+	kind: NotifyKind,
+}
+
+enum NotifyKind {
+	Play,
+	End,
+}
+
+#[async_trait]
+impl songbird::EventHandler for TrackNotifier {
+	async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+		let channel_id = {
+			let state = self.state.lock().await;
+			match state.announce_channels.get(&self.guild_id) {
+				Some(channel_id) => *channel_id,
+				None => return None,
+			}
+		};
+
+		match self.kind {
+			NotifyKind::Play => {
+				let title = match ctx {
+					songbird::EventContext::Track(tracks) => match tracks.first() {
+						Some((_, handle)) => handle.typemap().read().await
+							.get::<TrackTitleKey>()
+							.cloned()
+							.unwrap_or_else(|| "a track".to_string()),
+						None => return None,
+					},
+					_ => return None,
+				};
+
+				announce(&self.http, channel_id, &std::format!("Now playing {}", title)).await;
+			},
+			NotifyKind::End => {
+				// Decrement our own enqueue counter; when it reaches zero the
+				// queue has genuinely drained, regardless of whether songbird has
+				// popped the finished track yet.
+				let counter = {
+					let mut state = self.state.lock().await;
+					state.queue_counter(self.guild_id)
+				};
+
+				let remaining = match counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| value.checked_sub(1)) {
+					Ok(previous) => previous - 1,
+					// Nothing was outstanding; ignore this spurious end.
+					Err(_) => return None,
+				};
+
+				if remaining == 0 {
+					announce(&self.http, channel_id, "Queue finished").await;
+				}
+			},
+		}
+
+		return None;
+	}
+}
+
+/// Posts a single-line embed into `channel_id`. A failed send is dropped
+/// silently; unlike `check_msg`, nothing is logged.
+async fn announce(http: &Arc<Http>, channel_id: ChannelId, title: &str) {
+	let message = CreateMessage::new().embed(CreateEmbed::new().title(title));
+	let _ = channel_id.send_message(http, message).await;
+}
+
+/// Enqueues `source`, tagging the resulting track with `title` so the
+/// `Now playing` notifier can name it, and returns the new queue length.
+async fn enqueue_with_title(handler: &mut Call, source: songbird::input::Input, title: String) -> usize {
+	let handle = handler.enqueue_input(source).await;
+	handle.typemap().write().await.insert::<TrackTitleKey>(title);
+	return handler.queue().len();
+}
+
+/// Records one more in-flight track for `guild_id` so the `TrackEvent::End`
+/// notifier can tell when the queue has drained.
+async fn bump_queue_depth(ctx: &Context, guild_id: GuildId) {
+	if let Some(state) = ctx.data.read().await.get::<HandlerStateKey>().cloned() {
+		let counter = {
+			let mut state = state.lock().await;
+			state.queue_counter(guild_id)
+		};
+		counter.fetch_add(1, Ordering::SeqCst);
+	}
 }
 
 struct Handler {
@@ -62,18 +205,57 @@ impl EventHandler for Handler {
 	async fn ready(&self, ctx: Context, ready: Ready) {
 		println!("{} is connected!", ready.user.name);
 		let mut state = self.state.lock().await;
-		state.guilds = ready.guilds.clone();
+		state.guilds = ready.guilds.iter().map(|guild| guild.id).collect();
 		state.ctx = Some(ctx);
 	}
 }
 
 #[group]
-#[commands(deafen, join, leave, mute, play, ping, undeafen, unmute)]
+#[commands(deafen, join, leave, mute, play, playurl, ping, seek, skip, stop, pause, resume, reload, list, search, undeafen, unmute)]
 struct General;
 
-static mut GLOBA_SOUNDS_DIR: String = String::new();
 
-async fn find_active_voice_channel(state: &HandlerState) -> Result<Arc<Mutex<Call>>, String> {
+/// Installs the `Now playing` / `Queue finished` notifiers on a guild's call.
+///
+/// Re-registered on each join; existing global handlers are cleared first so a
+/// rejoin doesn't stack duplicate announcements.
+async fn install_track_notifiers(ctx: &Context, guild_id: GuildId, call: &Arc<Mutex<Call>>) {
+	let state = match ctx.data.read().await.get::<HandlerStateKey>().cloned() {
+		Some(state) => state,
+		None => return,
+	};
+
+	let mut handler = call.lock().await;
+	handler.remove_all_global_events();
+
+	handler.add_global_event(
+		Event::Track(TrackEvent::Play),
+		TrackNotifier {
+			guild_id,
+			http: ctx.http.clone(),
+			state: state.clone(),
+			kind: NotifyKind::Play,
+		},
+	);
+
+	handler.add_global_event(
+		Event::Track(TrackEvent::End),
+		TrackNotifier {
+			guild_id,
+			http: ctx.http.clone(),
+			state,
+			kind: NotifyKind::End,
+		},
+	);
+}
+
+async fn set_announce_channel(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) {
+	if let Some(state) = ctx.data.read().await.get::<HandlerStateKey>().cloned() {
+		state.lock().await.announce_channels.insert(guild_id, channel_id);
+	}
+}
+
+async fn find_active_voice_channel(state: &HandlerState) -> Result<(GuildId, Arc<Mutex<Call>>), String> {
 
 	let guilds = &state.guilds;
 	let ctx = match state.ctx.as_ref() {
@@ -81,19 +263,12 @@ async fn find_active_voice_channel(state: &HandlerState) -> Result<Arc<Mutex<Cal
 		_ => return Err("State is uninitialized".to_string()),
 	};
 
-	for guild in guilds {
-		let guild_id = match guild {
-			GuildStatus::OnlinePartialGuild(_) => guild.id(),
-			GuildStatus::OnlineGuild(guild) => guild.id,
-			GuildStatus::Offline(guild) => guild.id,
-			_ => panic!(),
-		};
-
+	for &guild_id in guilds {
 		let manager = songbird::get(ctx).await
 			.expect("Songbird Voice client placed in at initialisation.").clone();
 
 		if let Some(voice_channel) = manager.get(guild_id) {
-			return Ok(voice_channel);
+			return Ok((guild_id, voice_channel));
 		}
 	};
 
@@ -102,11 +277,23 @@ async fn find_active_voice_channel(state: &HandlerState) -> Result<Arc<Mutex<Cal
 
 async fn process_input(input: &str, state: Arc<Mutex<HandlerState>>) -> Result<String, String> {
 	let state = state.lock().await;
-	
 
-	let path = match find_path_for_name(&input) {
-		Some(path) => path,
-		_ => {
+	let ctx = match state.ctx.as_ref() {
+		Some(ctx) => ctx,
+		_ => return Err("State is uninitialized".to_string()),
+	};
+
+	let library = match ctx.data.read().await.get::<SoundLibraryKey>().cloned() {
+		Some(library) => library,
+		None => return Err("Sound library is uninitialized".to_string()),
+	};
+
+	let path = match library.lock().await.lookup(input) {
+		Lookup::Found(path, _) => path,
+		Lookup::Ambiguous(candidates) => {
+			return Err(std::format!("Did you mean: {}", candidates.join(", ")));
+		},
+		Lookup::NotFound => {
 			return Err("no matching file found".to_string());
 		}
 	};
@@ -117,49 +304,201 @@ async fn process_input(input: &str, state: Arc<Mutex<HandlerState>>) -> Result<S
 			return Err("Invalid path".to_string());
 		}
 	};
-	
 
-	let source = match songbird::input::ffmpeg(path).await {
+	let source = match local_source(&path) {
 		Ok(source) => source,
-		_ => {
-			return Err(std::format!("Invalid file: {}", file_name).to_string());
+		Err(why) => {
+			return Err(std::format!("Invalid file {}: {}", file_name, why).to_string());
 		},
 	};
 
-	let voice_channel = find_active_voice_channel(&state).await?;
+	let (guild_id, voice_channel) = find_active_voice_channel(&state).await?;
+
+	bump_queue_depth(ctx, guild_id).await;
 
 	let mut voice_channel = voice_channel.lock().await;
-	voice_channel.play_source(source);
+	enqueue_with_title(&mut voice_channel, source, file_name.clone()).await;
 
-	return Ok(std::format!("Playing {}", file_name));
+	return Ok(std::format!("Queued {}", file_name));
 }
 
-async fn command_line_loop(state: Arc<Mutex<HandlerState>>, shard_manager: Arc<Mutex<ShardManager>>) {
-	
+async fn cli_join(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> String {
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	match manager.join(guild_id, channel_id).await {
+		Ok(call) => {
+			install_track_notifiers(ctx, guild_id, &call).await;
+			std::format!("Joined {} / {}", guild_id, channel_id)
+		},
+		Err(why) => std::format!("Failed to join: {:?}", why),
+	}
+}
+
+async fn cli_queue_control(ctx: &Context, guild_id: GuildId, action: &str) -> String {
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	let call = match manager.get(guild_id) {
+		Some(call) => call,
+		None => return std::format!("No active call in guild {}", guild_id),
+	};
+
+	let handler = call.lock().await;
+	let queue = handler.queue();
+
+	let result = match action {
+		"skip" => queue.skip().map(|_| std::format!("Skipped ({} left in queue)", queue.len())),
+		"stop" => {
+			queue.stop();
+			return "Queue stopped".to_string();
+		},
+		"pause" => queue.pause().map(|_| "Paused".to_string()),
+		"resume" => queue.resume().map(|_| "Resumed".to_string()),
+		_ => return std::format!("Unknown queue action: {}", action),
+	};
+
+	return match result {
+		Ok(msg) => msg,
+		Err(why) => std::format!("Failed: {:?}", why),
+	};
+}
+
+async fn cli_queue_list(ctx: &Context, guild_id: GuildId) -> String {
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	let call = match manager.get(guild_id) {
+		Some(call) => call,
+		None => return std::format!("No active call in guild {}", guild_id),
+	};
+
+	let handler = call.lock().await;
+	return std::format!("{} track(s) in queue", handler.queue().len());
+}
+
+async fn enqueue_in_guild(ctx: &Context, guild_id: GuildId, name: &str) -> Result<String, String> {
+	let library = match ctx.data.read().await.get::<SoundLibraryKey>().cloned() {
+		Some(library) => library,
+		None => return Err("Sound library is uninitialized".to_string()),
+	};
+
+	let (path, file_name) = match library.lock().await.lookup(name) {
+		Lookup::Found(path, file_name) => (path, file_name),
+		Lookup::Ambiguous(candidates) => {
+			return Err(std::format!("Did you mean: {}", candidates.join(", ")));
+		},
+		Lookup::NotFound => return Err("no matching file found".to_string()),
+	};
+
+	let source = local_source(&path)?;
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	let call = match manager.get(guild_id) {
+		Some(call) => call,
+		None => return Err(std::format!("No active call in guild {}", guild_id)),
+	};
+
+	bump_queue_depth(ctx, guild_id).await;
+	enqueue_with_title(&mut *call.lock().await, source, file_name.clone()).await;
+
+	return Ok(std::format!("Queued {}", file_name));
+}
+
+/// Parses a single CLI line and drives the bot, returning a line to print.
+///
+/// Commands target `selected`, the guild last picked with `join`, so an
+/// operator can drive any guild the bot is in rather than the first active call
+/// the loop happens to find. `selected` is updated in place on `join`.
+async fn process_cli_command(
+	line: &str,
+	state: &Arc<Mutex<HandlerState>>,
+	selected: &mut Option<GuildId>,
+) -> String {
+	let ctx = match state.lock().await.ctx.clone() {
+		Some(ctx) => ctx,
+		None => return "State is uninitialized".to_string(),
+	};
+
+	let mut parts = line.split_whitespace();
+	let command = parts.next().unwrap_or("");
+
+	match command {
+		"join" => {
+			let guild_id = parts.next().and_then(|arg| arg.parse::<u64>().ok());
+			let channel_id = parts.next().and_then(|arg| arg.parse::<u64>().ok());
+			match (guild_id, channel_id) {
+				(Some(guild_id), Some(channel_id)) => {
+					*selected = Some(GuildId::new(guild_id));
+					cli_join(&ctx, GuildId::new(guild_id), ChannelId::new(channel_id)).await
+				},
+				_ => "Usage: join <guild> <channel>".to_string(),
+			}
+		},
+		"skip" | "stop" | "pause" | "resume" => {
+			match selected {
+				Some(guild_id) => cli_queue_control(&ctx, *guild_id, command).await,
+				None => "No guild selected; use `join <guild> <channel>` first".to_string(),
+			}
+		},
+		"queue" => {
+			match selected {
+				Some(guild_id) => cli_queue_list(&ctx, *guild_id).await,
+				None => "No guild selected; use `join <guild> <channel>` first".to_string(),
+			}
+		},
+		"play" => {
+			let name = parts.collect::<Vec<&str>>().join(" ");
+			if name.is_empty() {
+				return "Usage: play <name>".to_string();
+			}
+			match selected {
+				Some(guild_id) => match enqueue_in_guild(&ctx, *guild_id, &name).await {
+					Ok(msg) => msg,
+					Err(msg) => msg,
+				},
+				None => "No guild selected; use `join <guild> <channel>` first".to_string(),
+			}
+		},
+		_ => {
+			// Fall back to the legacy behaviour: treat the whole line as a sound
+			// name and play it in the first active call we can find.
+			match process_input(line, state.clone()).await {
+				Ok(msg) => msg,
+				Err(msg) => msg,
+			}
+		},
+	}
+}
+
+async fn command_line_loop(state: Arc<Mutex<HandlerState>>, shard_manager: Arc<ShardManager>) {
+
 	println!("Starting command line interface");
 
 	let stdin = async_std::io::stdin();
-	
-	loop { 
+
+	let mut selected: Option<GuildId> = None;
+
+	loop {
 		let mut line = String::new();
 		let result = stdin.read_line(&mut line).await;
 
 		if result.is_ok() {
 			match line.as_str().trim() {
-				"exit" => { 
-					shard_manager.lock().await.shutdown_all().await;
+				"exit" => {
+					shard_manager.shutdown_all().await;
 					break;
 				},
-				line => { 
-					match process_input(line, state.clone()).await {
-						Ok(msg) => { println!("{}", msg) },
-						Err(msg) => { println!("{}", msg) },
-					}
+				line => {
+					let msg = process_cli_command(line, &state, &mut selected).await;
+					println!("{}", msg);
 				},
 			}
 		}
 	}
-	
+
 
 
 }
@@ -167,8 +506,7 @@ async fn command_line_loop(state: Arc<Mutex<HandlerState>>, shard_manager: Arc<M
 #[command]
 #[only_in(guilds)]
 async fn deafen(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
@@ -200,12 +538,14 @@ async fn deafen(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
-	let channel_id = guild
-		.voice_states.get(&msg.author.id)
-		.and_then(|voice_state| voice_state.channel_id);
+	let channel_id = {
+		let guild = ctx.cache.guild(guild_id).unwrap();
+		guild
+			.voice_states.get(&msg.author.id)
+			.and_then(|voice_state| voice_state.channel_id)
+	};
 
 	let connect_to = match channel_id {
 		Some(channel) => channel,
@@ -219,7 +559,9 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
 
-	let _handler = manager.join(guild_id, connect_to).await;
+	if let Ok(call) = manager.join(guild_id, connect_to).await {
+		install_track_notifiers(ctx, guild_id, &call).await;
+	}
 
 	Ok(())
 }
@@ -227,8 +569,7 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
@@ -250,8 +591,7 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 async fn mute(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
@@ -352,48 +692,184 @@ fn soundboard_sanitize(str: &str) -> String {
 	return str;
 }
 
-fn soundboard_compare(file_name: &str, name_chunk: &str) -> bool {
-	let file_name = soundboard_sanitize(file_name);
-	let name_chunk = soundboard_sanitize(name_chunk);
+/// A single indexed sound file, keeping its path alongside the sanitized form
+/// of its file name so lookups never have to touch the filesystem or re-run
+/// `soundboard_sanitize` per query.
+struct SoundEntry {
+	path: PathBuf,
+	sanitized: String,
+}
 
-	return file_name.starts_with(&name_chunk);
+/// The in-memory index of the sounds directory, built once at startup and
+/// refreshed on demand via the `reload` command. Stored in the `TypeMap` so
+/// both the chat commands and the CLI loop share the same index.
+struct SoundLibrary {
+	root: PathBuf,
+	entries: Vec<SoundEntry>,
 }
 
+struct SoundLibraryKey;
 
-fn find_path_for_name(name: &str) -> Option<std::path::PathBuf> {
-	let mut files = Vec::<std::path::PathBuf>::new();
+impl TypeMapKey for SoundLibraryKey {
+	type Value = Arc<Mutex<SoundLibrary>>;
+}
 
-	let sounds_dir = unsafe {
-		GLOBA_SOUNDS_DIR.clone()
-	};
+impl SoundLibrary {
+	fn index(root: PathBuf) -> SoundLibrary {
+		let mut entries = Vec::new();
+		index_dir(&root, &mut entries);
+		return SoundLibrary { root, entries };
+	}
 
-	for entry in std::fs::read_dir(sounds_dir).ok()? {
-		let entry = entry.ok()?;
+	fn reload(&mut self) {
+		let mut entries = Vec::new();
+		index_dir(&self.root, &mut entries);
+		self.entries = entries;
+	}
 
-		let path = entry.path();
-		if path.is_dir() {
-			// visit_dirs(&path, cb)?;
-		} else {
-			files.push(path.clone());
+	/// All entries scored against `query`, best match first.
+	fn rank(&self, query: &str) -> Vec<Scored> {
+		let query = soundboard_sanitize(query);
+		let mut scored: Vec<Scored> = self.entries
+			.iter()
+			.filter_map(|entry| {
+				let name = entry.path.file_name().and_then(|file_name| file_name.to_str())?.to_string();
+				let score = match_score(&entry.sanitized, &query);
+				Some(Scored { path: entry.path.clone(), name, score })
+			})
+			.collect();
+		scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+		return scored;
+	}
+
+	/// Resolves `query` to a single sound, or reports when no candidate clears
+	/// the threshold or several are too close to pick between.
+	fn lookup(&self, query: &str) -> Lookup {
+		let ranked = self.rank(query);
+
+		let best = match ranked.first() {
+			Some(best) if best.score >= MATCH_THRESHOLD => best,
+			_ => return Lookup::NotFound,
+		};
+
+		let close: Vec<String> = ranked
+			.iter()
+			.take_while(|scored| scored.score >= MATCH_THRESHOLD && best.score - scored.score <= MATCH_MARGIN)
+			.map(|scored| scored.name.clone())
+			.collect();
+
+		if close.len() > 1 {
+			return Lookup::Ambiguous(close.into_iter().take(5).collect());
 		}
+
+		return Lookup::Found(best.path.clone(), best.name.clone());
+	}
+
+	fn search(&self, query: &str) -> Vec<String> {
+		return self.rank(query)
+			.into_iter()
+			.filter(|scored| scored.score >= MATCH_THRESHOLD)
+			.map(|scored| scored.name)
+			.collect();
+	}
+}
+
+/// A candidate sound name with its fuzzy-match score against a query.
+struct Scored {
+	path: PathBuf,
+	name: String,
+	score: f64,
+}
+
+/// Outcome of resolving a query against the [`SoundLibrary`].
+enum Lookup {
+	Found(PathBuf, String),
+	Ambiguous(Vec<String>),
+	NotFound,
+}
+
+/// Minimum score a candidate must reach to be considered a match at all.
+const MATCH_THRESHOLD: f64 = 0.4;
+/// Candidates within this score of the best are treated as ambiguous.
+const MATCH_MARGIN: f64 = 0.08;
+
+fn levenshtein(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.chars().collect();
+	let b: Vec<char> = b.chars().collect();
+
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	let mut curr: Vec<usize> = vec![0; b.len() + 1];
+
+	for i in 1..=a.len() {
+		curr[0] = i;
+		for j in 1..=b.len() {
+			let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+			curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+		}
+		std::mem::swap(&mut prev, &mut curr);
+	}
+
+	return prev[b.len()];
+}
+
+/// Fuzzy match score in `0.0..=1.0` (higher is better) of a sanitized `query`
+/// against a sanitized candidate `name`. A substring hit scores by how much of
+/// the name it covers, so an exact name scores `1.0`; otherwise the score falls
+/// back to a Levenshtein ratio to tolerate typos.
+fn match_score(name: &str, query: &str) -> f64 {
+	if query.is_empty() || name.is_empty() {
+		return 0.0;
 	}
 
-	let extract_name = |path: &std::path::PathBuf| -> String {
-		path
-			.file_name()
-			.and_then(|path| path.to_str())
-			.unwrap_or("NOFILENAMEERROR")
-			.to_string()
+	if name.contains(query) {
+		return 0.5 + 0.5 * (query.len() as f64 / name.len() as f64);
+	}
+
+	let distance = levenshtein(name, query);
+	let max_len = name.len().max(query.len());
+	return 1.0 - (distance as f64 / max_len as f64);
+}
+
+fn index_dir(dir: &Path, out: &mut Vec<SoundEntry>) {
+	let read_dir = match std::fs::read_dir(dir) {
+		Ok(read_dir) => read_dir,
+		Err(_) => return,
 	};
 
-	let path = files
-		.iter()
-		.filter(|&path| soundboard_compare(&extract_name(path), name))
-		.next()?;
-		//.and_then(|path| Some(path.clone()))?;
+	for entry in read_dir {
+		let path = match entry {
+			Ok(entry) => entry.path(),
+			Err(_) => continue,
+		};
 
-	
-	return Some(path.clone());
+		if path.is_dir() {
+			index_dir(&path, out);
+		} else if path.file_name().and_then(|file_name| file_name.to_str()).is_some() {
+			// Index on the extension-less stem so a user-typed name (which never
+			// carries `.mp3`/`.wav`) can score an exact `1.0` against it.
+			let stem = path.file_stem()
+				.and_then(|stem| stem.to_str())
+				.unwrap_or_default();
+			let sanitized = soundboard_sanitize(stem);
+			out.push(SoundEntry { path: path.clone(), sanitized });
+		}
+	}
+}
+
+/// Builds an in-process Symphonia-decoded input for a local file.
+///
+/// `songbird::input::File` demuxes and decodes the container in-process via
+/// Symphonia (no `ffmpeg` child), which is what lets `TrackHandle::seek` reposition
+/// the stream without re-launching an external process.
+fn local_source(path: &Path) -> Result<songbird::input::Input, String> {
+	if !path.exists() {
+		return Err(std::format!("File not found: {:?}", path));
+	}
+
+	// `File` stores the path by value, so it must own a `PathBuf` rather than
+	// borrow our `&Path` argument.
+	#[allow(clippy::unnecessary_to_owned)]
+	return Ok(songbird::input::File::new(path.to_path_buf()).into());
 }
 
 #[command]
@@ -410,36 +886,51 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	};
 
 	
-	let path = match find_path_for_name(&file_name_chunk) {
-		Some(path) => path,
-		_ => {
+	let library = match ctx.data.read().await.get::<SoundLibraryKey>().cloned() {
+		Some(library) => library,
+		None => {
+			check_msg(msg.channel_id.say(&ctx.http, "Sound library is uninitialized").await);
+			return Ok(());
+		}
+	};
+
+	let (path, title) = match library.lock().await.lookup(&file_name_chunk) {
+		Lookup::Found(path, title) => (path, title),
+		Lookup::Ambiguous(candidates) => {
+			check_msg(msg.channel_id.say(&ctx.http, format!("Did you mean: {}", candidates.join(", "))).await);
+			return Ok(());
+		},
+		Lookup::NotFound => {
 			check_msg(msg.channel_id.say(&ctx.http, "no matching file found").await);
 			return Ok(());
 		}
 	};
 
-	let source = match songbird::input::ffmpeg(path).await {
+	let source = match local_source(&path) {
 		Ok(source) => source,
-		_ => {
-			check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
+		Err(why) => {
+			check_msg(msg.channel_id.say(&ctx.http, std::format!("Error decoding file: {}", why)).await);
 			return Ok(());
 		},
 	};
 
 
 
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
 
+	set_announce_channel(ctx, guild_id, msg.channel_id).await;
+
 	if let Some(handler_lock) = manager.get(guild_id) {
+		bump_queue_depth(ctx, guild_id).await;
+
 		let mut handler = handler_lock.lock().await;
-		
-		handler.play_source(source);
 
-		let reply_msg = std::format!("Playing song {}", 2);
+		let queued = enqueue_with_title(&mut handler, source, title).await;
+
+		let reply_msg = std::format!("Queued song ({} in queue)", queued);
 		check_msg(msg.channel_id.say(&ctx.http, reply_msg).await);
 	} else {
 		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
@@ -448,11 +939,255 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	Ok(())
 }
 
+#[command]
+#[only_in(guilds)]
+async fn playurl(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+
+	let url = match args.single::<String>() {
+		Ok(url) => url,
+		Err(_) => {
+			check_msg(msg.channel_id.say(&ctx.http, "Must provide a URL to a video or audio").await);
+
+			return Ok(());
+		},
+	};
+
+	if !url.starts_with("http") {
+		check_msg(msg.channel_id.say(&ctx.http, "Must provide a valid URL").await);
+
+		return Ok(());
+	}
+
+	let http_client = {
+		let data = ctx.data.read().await;
+		data.get::<HttpKey>()
+			.cloned()
+			.expect("HttpKey placed in at initialisation.")
+	};
+
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	set_announce_channel(ctx, guild_id, msg.channel_id).await;
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		bump_queue_depth(ctx, guild_id).await;
+
+		let mut handler = handler_lock.lock().await;
+
+		let source = songbird::input::YoutubeDl::new(http_client, url.clone());
+		let queued = enqueue_with_title(&mut handler, source.into(), url).await;
+
+		let reply_msg = std::format!("Queued link ({} in queue)", queued);
+		check_msg(msg.channel_id.say(&ctx.http, reply_msg).await);
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn seek(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let seconds = match args.single::<u64>() {
+		Ok(seconds) => seconds,
+		Err(_) => {
+			check_msg(msg.channel_id.say(&ctx.http, "Must provide a position in seconds").await);
+
+			return Ok(());
+		},
+	};
+
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		let handler = handler_lock.lock().await;
+
+		match handler.queue().current() {
+			Some(track) => {
+				if let Err(e) = track.seek_async(Duration::from_secs(seconds)).await {
+					check_msg(msg.channel_id.say(&ctx.http, format!("Failed: {:?}", e)).await);
+				}
+
+				check_msg(msg.channel_id.say(&ctx.http, format!("Seeked to {}s", seconds)).await);
+			},
+			None => {
+				check_msg(msg.channel_id.say(&ctx.http, "Nothing is playing").await);
+			},
+		}
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		let handler = handler_lock.lock().await;
+		let queue = handler.queue();
+		if let Err(e) = queue.skip() {
+			check_msg(msg.channel_id.say(&ctx.http, format!("Failed: {:?}", e)).await);
+		}
+
+		check_msg(msg.channel_id.say(&ctx.http, format!("Skipped ({} left in queue)", queue.len())).await);
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		let handler = handler_lock.lock().await;
+		let queue = handler.queue();
+		queue.stop();
+
+		check_msg(msg.channel_id.say(&ctx.http, "Queue stopped").await);
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		let handler = handler_lock.lock().await;
+		let queue = handler.queue();
+		if let Err(e) = queue.pause() {
+			check_msg(msg.channel_id.say(&ctx.http, format!("Failed: {:?}", e)).await);
+		}
+
+		check_msg(msg.channel_id.say(&ctx.http, "Paused").await);
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+	let guild_id = msg.guild_id.unwrap();
+
+	let manager = songbird::get(ctx).await
+		.expect("Songbird Voice client placed in at initialisation.").clone();
+
+	if let Some(handler_lock) = manager.get(guild_id) {
+		let handler = handler_lock.lock().await;
+		let queue = handler.queue();
+		if let Err(e) = queue.resume() {
+			check_msg(msg.channel_id.say(&ctx.http, format!("Failed: {:?}", e)).await);
+		}
+
+		check_msg(msg.channel_id.say(&ctx.http, "Resumed").await);
+	} else {
+		check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
+	}
+
+	Ok(())
+}
+
+#[command]
+async fn reload(ctx: &Context, msg: &Message) -> CommandResult {
+	let library = match ctx.data.read().await.get::<SoundLibraryKey>().cloned() {
+		Some(library) => library,
+		None => {
+			check_msg(msg.channel_id.say(&ctx.http, "Sound library is uninitialized").await);
+			return Ok(());
+		}
+	};
+
+	let count = {
+		let mut library = library.lock().await;
+		library.reload();
+		library.entries.len()
+	};
+
+	check_msg(msg.channel_id.say(&ctx.http, format!("Reloaded sound library ({} sounds)", count)).await);
+
+	Ok(())
+}
+
+#[command]
+async fn list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+	let query = args.message().trim();
+
+	let library = match ctx.data.read().await.get::<SoundLibraryKey>().cloned() {
+		Some(library) => library,
+		None => {
+			check_msg(msg.channel_id.say(&ctx.http, "Sound library is uninitialized").await);
+			return Ok(());
+		}
+	};
+
+	let names: Vec<String> = if query.is_empty() {
+		library.lock().await.entries
+			.iter()
+			.filter_map(|entry| entry.path.file_name().and_then(|file_name| file_name.to_str()))
+			.map(|file_name| file_name.to_string())
+			.collect()
+	} else {
+		library.lock().await.search(query)
+	};
+
+	if names.is_empty() {
+		check_msg(msg.channel_id.say(&ctx.http, "No sounds found").await);
+		return Ok(());
+	}
+
+	// Keep the reply within Discord's message limit by only listing the first
+	// page of matches.
+	let shown: Vec<String> = names.iter().take(25).cloned().collect();
+	let mut reply = shown.join(", ");
+	if names.len() > shown.len() {
+		reply.push_str(&format!(" … (+{} more)", names.len() - shown.len()));
+	}
+
+	check_msg(msg.channel_id.say(&ctx.http, reply).await);
+
+	Ok(())
+}
+
+#[command]
+async fn search(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+	list(ctx, msg, args).await
+}
+
 #[command]
 #[only_in(guilds)]
 async fn undeafen(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
@@ -474,8 +1209,7 @@ async fn undeafen(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 async fn unmute(ctx: &Context, msg: &Message) -> CommandResult {
-	let guild = msg.guild(&ctx.cache).await.unwrap();
-	let guild_id = guild.id;
+	let guild_id = msg.guild_id.unwrap();
 	
 	let manager = songbird::get(ctx).await
 		.expect("Songbird Voice client placed in at initialisation.").clone();
@@ -511,25 +1245,30 @@ async fn main() {
 		.expect("Expected (DISCORD_TOKEN) in the environment");
 
 
-	unsafe {
-		GLOBA_SOUNDS_DIR = env::var("SOUNDS_DIRECTORY")
-			.expect("Expected (SOUNDS_DIRECTORY) in the environment");    
-	}
-	
+	let sounds_dir = env::var("SOUNDS_DIRECTORY")
+		.expect("Expected (SOUNDS_DIRECTORY) in the environment");
+	let sound_library = Arc::new(Mutex::new(SoundLibrary::index(PathBuf::from(sounds_dir))));
+
 
 	let framework = StandardFramework::new()
-		.configure(|c| c
-				   .prefix("~"))
 		.group(&GENERAL_GROUP);
+	framework.configure(Configuration::new().prefix("~"));
 
 	let handler = Handler::new();
 
 	let state = handler.state.clone();
 
-	let mut client = Client::builder(&token)
+	let intents = GatewayIntents::non_privileged()
+		| GatewayIntents::MESSAGE_CONTENT
+		| GatewayIntents::GUILD_VOICE_STATES;
+
+	let mut client = Client::builder(&token, intents)
 		.event_handler(handler)
 		.framework(framework)
 		.register_songbird()
+		.type_map_insert::<HttpKey>(reqwest::Client::new())
+		.type_map_insert::<HandlerStateKey>(state.clone())
+		.type_map_insert::<SoundLibraryKey>(sound_library)
 		.await
 		.expect("Err creating client");
 
@@ -547,3 +1286,68 @@ async fn main() {
 	//let _ = client_task.await.map_err(|why| println!("Client ended: {:?}", why));
 	
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn library(names: &[&str]) -> SoundLibrary {
+		let entries = names
+			.iter()
+			.map(|name| SoundEntry {
+				path: PathBuf::from(std::format!("{}.mp3", name)),
+				sanitized: soundboard_sanitize(name),
+			})
+			.collect();
+		return SoundLibrary { root: PathBuf::new(), entries };
+	}
+
+	#[test]
+	fn levenshtein_counts_edits() {
+		assert_eq!(levenshtein("kitten", "sitting"), 3);
+		assert_eq!(levenshtein("cat", "cat"), 0);
+		assert_eq!(levenshtein("", "cat"), 3);
+	}
+
+	#[test]
+	fn exact_stem_scores_one() {
+		assert_eq!(match_score("cat", "cat"), 1.0);
+	}
+
+	#[test]
+	fn substring_beats_typo() {
+		assert!(match_score("catfight", "cat") > match_score("cat", "cta"));
+	}
+
+	#[test]
+	fn lookup_resolves_exact_match() {
+		match library(&["cat", "dog"]).lookup("cat") {
+			Lookup::Found(path, name) => {
+				assert_eq!(name, "cat.mp3");
+				assert_eq!(path, PathBuf::from("cat.mp3"));
+			},
+			_ => panic!("expected Found"),
+		}
+	}
+
+	#[test]
+	fn lookup_disambiguates_near_twins() {
+		match library(&["cab", "cat"]).lookup("ca") {
+			Lookup::Ambiguous(candidates) => assert_eq!(candidates.len(), 2),
+			_ => panic!("expected Ambiguous"),
+		}
+	}
+
+	#[test]
+	fn lookup_tolerates_typos() {
+		match library(&["hello", "world"]).lookup("helo") {
+			Lookup::Found(_, name) => assert_eq!(name, "hello.mp3"),
+			_ => panic!("expected Found"),
+		}
+	}
+
+	#[test]
+	fn lookup_rejects_garbage() {
+		assert!(matches!(library(&["cat"]).lookup("zzzzz"), Lookup::NotFound));
+	}
+}